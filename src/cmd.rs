@@ -0,0 +1,115 @@
+// `cmd!` builds an `unshare::Command` from a comma-separated list of
+// items, interpolating `{var}` and `{var, ...}` without ever
+// concatenating values into a shell line — there is no word-splitting
+// or shell-injection surface, analogous to xshell's macro but
+// targeting this crate's builder instead of a subprocess shell-out.
+//
+// Spec deviation: the request this was built from asked for bare,
+// whitespace-separated tokens (`cmd!(git clone {url})`), mirroring a
+// shell command line. That doesn't actually work here: Rust's
+// tokenizer splits a bare `-la` into the punctuation token `-` and the
+// ident `la`, and `/tmp` into `/`, `tmp` — plain `macro_rules!` has no
+// access to source spans/adjacency (that needs a proc-macro), so there
+// is no way to tell "these two token trees were written with no space
+// between them" from "the user wrote two separate arguments". The
+// original whitespace-separated design silently shredded any literal
+// containing `-` or `/`, which is most real flags and paths. Each
+// argument is therefore its own comma-separated item instead: a bare
+// identifier (for simple words), a string literal (for anything with
+// punctuation), or a `{...}` interpolation.
+
+/// Builds a [`Command`](::Command) from a comma-separated list of
+/// items.
+///
+/// The first item is the program name; the rest become arguments. Each
+/// item is a bare identifier (a simple word, e.g. `git`), a string
+/// literal (for flags/paths, e.g. `"-la"` or `"/tmp"`), or a `{expr}`
+/// that interpolates a single `OsStr`-convertible value as one
+/// argument. `{expr, ...}` splats an `IntoIterator` into multiple
+/// arguments instead (the comma before `...` is required: a
+/// `macro_rules` `expr` fragment may only be followed by `=>`, `,` or
+/// `;`). Namespace configuration (`chroot`, `uid_map`, ...) is left to
+/// be applied on the returned builder.
+///
+/// ```ignore
+/// let url = "https://example.com/repo.git".to_string();
+/// let extra_args = vec!["--depth".to_string(), "1".to_string()];
+/// let mut cmd = cmd!(git, "clone", { extra_args, ... }, { url });
+/// cmd.unshare(&[Namespace::Mount]);
+/// ```
+#[macro_export]
+macro_rules! cmd {
+    ($program:tt $(, $arg:tt)* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut cmd = $crate::Command::new($crate::__cmd_tok!($program));
+        $(
+            $crate::__cmd_push_arg!(cmd, $arg);
+        )*
+        cmd
+    }};
+}
+
+// Resolves a single program-name item to the expression passed to
+// `Command::new`. A separate macro (rather than another arm of `cmd!`
+// itself) for the same reason `__cmd_push_arg!` is: it's dispatched on
+// fragment kind (`literal` vs. bare `tt`), which is cleanest to do in
+// its own small table rather than folded into the entry rule.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __cmd_tok {
+    ({ $var:expr }) => { &$var };
+    ($lit:literal) => { $lit };
+    ($lit:tt) => { stringify!($lit) };
+}
+
+// Pushes a single `{var}` / `{var, ...}` / literal / bare-identifier
+// argument onto `$cmd`. `$lit:literal` must come before the catch-all
+// `$lit:tt` arm, since a string literal also matches `tt` but needs its
+// value used directly rather than re-stringified (`stringify!("-la")`
+// would otherwise produce the five-character text `"-la"` *including
+// the quotes*, not the three-character flag).
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __cmd_push_arg {
+    ($cmd:ident, { $var:expr, ... }) => {
+        $cmd.args(&$var.into_iter().collect::<::std::vec::Vec<_>>());
+    };
+    ($cmd:ident, { $var:expr }) => {
+        $cmd.arg(&$var);
+    };
+    ($cmd:ident, $lit:literal) => {
+        $cmd.arg($lit);
+    };
+    ($cmd:ident, $lit:tt) => {
+        $cmd.arg(stringify!($lit));
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use Command;
+
+    #[test]
+    fn literals_with_punctuation_are_not_shredded() {
+        let path = "/tmp".to_string();
+        let cmd = cmd!(ls, "-la", { path });
+        assert_eq!(cmd.args, vec![
+            CString::new("ls").unwrap(),
+            CString::new("-la").unwrap(),
+            CString::new("/tmp").unwrap(),
+        ]);
+    }
+
+    #[test]
+    fn splat_expands_into_multiple_args() {
+        let extra = vec!["-n", "5"];
+        let cmd = cmd!(head, { extra, ... }, "-q");
+        assert_eq!(cmd.args, vec![
+            CString::new("head").unwrap(),
+            CString::new("-n").unwrap(),
+            CString::new("5").unwrap(),
+            CString::new("-q").unwrap(),
+        ]);
+    }
+}
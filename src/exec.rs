@@ -0,0 +1,150 @@
+// Actually forks and execve's a `Command`. This is the call site the
+// builder methods in `std_api.rs` configure: PATH resolution, pty
+// allocation, and `$VAR` expansion all happen here, right before the
+// child replaces its process image.
+use std::ffi::{CString, OsStr};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::ptr;
+use std::time::Duration;
+
+use libc;
+
+use pty;
+use std_api::{expand_env_refs, wait_with_timeout};
+use Command;
+
+/// A spawned child process, returned by `Command::spawn`.
+pub struct Child {
+    pid: libc::pid_t,
+    timeout: Option<Duration>,
+    /// The pty master fd, when the `Command` had `pty()` set. The
+    /// caller owns it from here on: read/write it to drive the child,
+    /// and close it when done.
+    pub pty_master: Option<RawFd>,
+}
+
+impl Child {
+    /// Blocks until the child exits and collects its exit status.
+    ///
+    /// If the `Command` that spawned this child had a `timeout()` set
+    /// and the child hasn't exited by the deadline, it is killed and
+    /// reaped instead, and this returns an `io::Error` of kind
+    /// `TimedOut` rather than an exit status.
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        match self.timeout {
+            Some(timeout) => match wait_with_timeout(self.pid, timeout)? {
+                Some(status) => Ok(status),
+                None => Err(io::Error::new(io::ErrorKind::TimedOut,
+                    "child did not exit before its deadline and was killed")),
+            },
+            None => {
+                let mut raw_status: libc::c_int = 0;
+                let rc = unsafe { libc::waitpid(self.pid, &mut raw_status, 0) };
+                if rc < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(ExitStatus::from_raw(raw_status))
+            }
+        }
+    }
+}
+
+impl Command {
+    /// Executes the command as a child process, returning a handle to
+    /// it rather than waiting for it to complete.
+    ///
+    /// Resolves the program against `PATH` (via `resolve_executable`)
+    /// unless `allow_path_search(false)` was set or the name already
+    /// contains a `/`. When `pty()` was set, allocates the pty before
+    /// forking and makes the child its session leader with the slave
+    /// as controlling terminal on fds 0/1/2. The `stdin`/`stdout`/
+    /// `stderr` settings are not applied here, with or without a pty;
+    /// the child inherits the parent's fds unless a pty replaces them.
+    /// When `expand_args()` was set, expands
+    /// `$VAR`/`${VAR}` references in the arguments and `current_dir`
+    /// against the child's effective environment before handing them
+    /// to `execve`.
+    pub fn spawn(&mut self) -> io::Result<Child> {
+        self.init_env_map();
+        let exe = self.resolve_executable();
+
+        let argv: Vec<CString> = if self.config.expand_args {
+            let env = self.environ.as_ref().unwrap();
+            self.args.iter()
+                .map(|a| {
+                    let expanded = expand_env_refs(OsStr::from_bytes(a.as_bytes()), env);
+                    CString::new(expanded.as_bytes()).unwrap()
+                })
+                .collect()
+        } else {
+            self.args.clone()
+        };
+        let mut argv_ptrs: Vec<*const libc::c_char> =
+            argv.iter().map(|a| a.as_ptr()).collect();
+        argv_ptrs.push(ptr::null());
+
+        let envp: Vec<CString> = self.environ.as_ref().unwrap().iter()
+            .map(|(k, v)| {
+                let mut kv = k.as_bytes().to_vec();
+                kv.push(b'=');
+                kv.extend_from_slice(v.as_bytes());
+                CString::new(kv).unwrap()
+            })
+            .collect();
+        let mut envp_ptrs: Vec<*const libc::c_char> =
+            envp.iter().map(|e| e.as_ptr()).collect();
+        envp_ptrs.push(ptr::null());
+
+        let work_dir = match self.config.work_dir {
+            Some(ref dir) if self.config.expand_args => {
+                let expanded = expand_env_refs(
+                    OsStr::from_bytes(dir.as_bytes()), self.environ.as_ref().unwrap());
+                Some(CString::new(expanded.as_bytes()).unwrap())
+            }
+            Some(ref dir) => Some(dir.clone()),
+            None => None,
+        };
+
+        let pty_pair = match self.config.pty {
+            Some(ref cfg) => Some(pty::open_pty(cfg)?),
+            None => None,
+        };
+
+        let pid = unsafe { libc::fork() };
+        if pid < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if pid == 0 {
+            if let Some(ref pair) = pty_pair {
+                let _ = pty::attach_pty_in_child(pair.master, pair.slave);
+            }
+            unsafe {
+                if let Some(ref dir) = work_dir {
+                    libc::chdir(dir.as_ptr());
+                }
+                libc::execve(exe.as_ptr(), argv_ptrs.as_ptr(), envp_ptrs.as_ptr());
+                libc::_exit(127);
+            }
+        }
+
+        let pty_master = match pty_pair {
+            Some(pair) => {
+                unsafe { libc::close(pair.slave); }
+                Some(pair.master)
+            }
+            None => None,
+        };
+
+        Ok(Child { pid: pid, timeout: self.config.timeout, pty_master: pty_master })
+    }
+
+    /// Executes the command, waiting for it to finish and collecting
+    /// its exit status.
+    pub fn status(&mut self) -> io::Result<ExitStatus> {
+        self.spawn()?.wait()
+    }
+}
@@ -0,0 +1,113 @@
+// Pseudo-terminal support used by `Command::pty()`. A pty gives the
+// child a controlling terminal instead of plain pipes, which is what
+// interactive programs (shells, package managers that probe `isatty`)
+// need to behave sensibly when launched into a namespace.
+use std::ffi::CStr;
+use std::io;
+use std::mem;
+use std::os::unix::io::RawFd;
+
+use libc;
+
+/// Per-command PTY configuration, set up via `Command::pty()` and the
+/// `pty_size`/`pty_raw` modifiers.
+#[derive(Debug, Clone)]
+pub struct PtyConfig {
+    pub rows: u16,
+    pub cols: u16,
+    pub raw: bool,
+}
+
+impl Default for PtyConfig {
+    fn default() -> PtyConfig {
+        PtyConfig { rows: 24, cols: 80, raw: false }
+    }
+}
+
+/// An allocated master/slave pty pair, as returned by `open_pty`.
+///
+/// The slave is wired to fds 0/1/2 in the child (after `setsid` and
+/// `TIOCSCTTY`); the master is kept open in the parent and handed back
+/// to the caller on spawn.
+pub struct Pty {
+    pub master: RawFd,
+    pub slave: RawFd,
+}
+
+/// Allocates a new pty master/slave pair and applies `cfg`'s window
+/// size and line-discipline mode to the slave side.
+///
+/// Must run before `fork`, since the slave fd is inherited by the
+/// child and reopened there as fds 0/1/2.
+pub fn open_pty(cfg: &PtyConfig) -> io::Result<Pty> {
+    unsafe {
+        let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        if master < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::grantpt(master) < 0 || libc::unlockpt(master) < 0 {
+            let err = io::Error::last_os_error();
+            libc::close(master);
+            return Err(err);
+        }
+        let name_ptr = libc::ptsname(master);
+        if name_ptr.is_null() {
+            let err = io::Error::last_os_error();
+            libc::close(master);
+            return Err(err);
+        }
+        let name = CStr::from_ptr(name_ptr).to_owned();
+        let slave = libc::open(name.as_ptr(), libc::O_RDWR | libc::O_NOCTTY);
+        if slave < 0 {
+            let err = io::Error::last_os_error();
+            libc::close(master);
+            return Err(err);
+        }
+
+        let mut size: libc::winsize = mem::zeroed();
+        size.ws_row = cfg.rows;
+        size.ws_col = cfg.cols;
+        libc::ioctl(slave, libc::TIOCSWINSZ, &size);
+
+        if cfg.raw {
+            let mut term: libc::termios = mem::zeroed();
+            if libc::tcgetattr(slave, &mut term) == 0 {
+                libc::cfmakeraw(&mut term);
+                libc::tcsetattr(slave, libc::TCSANOW, &term);
+            }
+        }
+
+        Ok(Pty { master: master, slave: slave })
+    }
+}
+
+/// Makes the calling process (expected to be the freshly-forked child)
+/// a session leader and gives it `slave` as its controlling terminal,
+/// then duplicates `slave` onto fds 0, 1 and 2.
+///
+/// Called in the child between `fork` and `execve`, before the target
+/// program replaces the process image.
+/// `master` is the parent's end of the same pair; it must be closed
+/// here too, otherwise the child (and whatever it `execve`s) inherits
+/// it, which can both wedge the parent's reads on `master` (no EOF) and
+/// leak the fd into the sandboxed program.
+pub fn attach_pty_in_child(master: RawFd, slave: RawFd) -> io::Result<()> {
+    unsafe {
+        if libc::setsid() < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::ioctl(slave, libc::TIOCSCTTY, 0) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        libc::close(master);
+        for fd in 0..3 {
+            if libc::dup2(slave, fd) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        if slave > 2 {
+            libc::close(slave);
+        }
+        Ok(())
+    }
+}
@@ -5,14 +5,24 @@
 // file at the top-level directory of this distribution and at
 // http://rust-lang.org/COPYRIGHT.
 //
-use std::ffi::OsStr;
+use std::ffi::{CString, OsStr};
 use std::default::Default;
 use std::collections::HashMap;
 use std::env;
-use std::path::Path;
-use std::process::Stdio;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use libc;
 
 use ffi_util::ToCString;
+use pty::PtyConfig;
 use Command;
 
 impl Command {
@@ -50,6 +60,53 @@ impl Command {
         self
     }
 
+    /// Controls whether a `filename` with no `/` is looked up on `PATH`
+    /// before `execve`, as opposed to being passed through verbatim.
+    ///
+    /// This is enabled by default, mirroring the lookup std's own process
+    /// spawner does. The lookup happens at spawn time, in the parent
+    /// before `fork`, so directories are probed against the parent's
+    /// root; a chroot/pivot_root of the child is not taken into account.
+    /// The `PATH` used is taken from the configured environment
+    /// (`env`/`envs`) when it contains one, falling back to the parent's
+    /// own `PATH` otherwise (including after `env_clear`).
+    ///
+    /// Disable this if you want the literal `filename` handed to the
+    /// kernel, and an `ENOENT` if it can't find it.
+    pub fn allow_path_search(&mut self, allow: bool) -> &mut Command {
+        self.config.allow_path_search = allow;
+        self
+    }
+
+    /// Resolves `self.filename` to the path that should actually be
+    /// `execve`'d, performing a `PATH` search unless one has been
+    /// disabled with `allow_path_search(false)` or the name already
+    /// contains a `/`.
+    ///
+    /// Candidate paths are probed with `fs::metadata` in the calling
+    /// process, so they are checked against that process's root.
+    pub(crate) fn resolve_executable(&self) -> CString {
+        if !self.config.allow_path_search ||
+            self.filename.as_bytes().contains(&b'/')
+        {
+            return self.filename.clone();
+        }
+        let path = self.environ.as_ref()
+            .and_then(|e| e.get(OsStr::new("PATH")).cloned())
+            .or_else(|| env::var_os("PATH"));
+        let path = match path {
+            Some(path) => path,
+            None => return self.filename.clone(),
+        };
+        for dir in env::split_paths(&path) {
+            let candidate = dir.join(OsStr::from_bytes(self.filename.as_bytes()));
+            if is_executable_file(&candidate) {
+                return candidate.to_cstring();
+            }
+        }
+        self.filename.clone()
+    }
+
     // TODO(tailhook) It's only public for our run module any better way?
     pub fn init_env_map(&mut self) {
         if self.environ.is_none() {
@@ -68,6 +125,22 @@ impl Command {
         self
     }
 
+    /// Inserts or updates environment variable mappings from an iterator
+    /// of key/value pairs, e.g. another process's environment or a
+    /// `HashMap`. Useful for building up a minimal, explicit environment
+    /// for a sandboxed child without a call to `env` per variable.
+    pub fn envs<I, K, V>(&mut self, vars: I) -> &mut Command
+        where I: IntoIterator<Item=(K, V)>, K: AsRef<OsStr>, V: AsRef<OsStr>
+    {
+        self.init_env_map();
+        for (key, val) in vars {
+            self.environ.as_mut().unwrap().insert(
+                key.as_ref().to_os_string(),
+                val.as_ref().to_os_string());
+        }
+        self
+    }
+
     /// Removes an environment variable mapping.
     pub fn env_remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Command {
         self.init_env_map();
@@ -75,12 +148,36 @@ impl Command {
         self
     }
 
+    /// Removes every environment variable for which `f` returns `false`,
+    /// e.g. to prune a mostly-inherited environment down to an allowlist
+    /// before handing it to a sandboxed child.
+    pub fn env_retain<F>(&mut self, mut f: F) -> &mut Command
+        where F: FnMut(&OsStr, &OsStr) -> bool
+    {
+        self.init_env_map();
+        self.environ.as_mut().unwrap().retain(|k, v| f(k, v));
+        self
+    }
+
     /// Clears the entire environment map for the child process.
     pub fn env_clear(&mut self) -> &mut Command {
         self.environ = Some(HashMap::new());
         self
     }
 
+    /// Enables `$VAR`/`${VAR}` expansion in arguments and `current_dir`
+    /// against the child's effective environment (the same map that will
+    /// be passed to the child) at spawn time.
+    ///
+    /// Lets a caller set a custom `PATH`/`HOME` once via `env`/`envs` and
+    /// have argument templates resolve consistently inside the new
+    /// namespace, rather than against the parent's environment. Disabled
+    /// by default; references that don't resolve are left untouched.
+    pub fn expand_args(&mut self) -> &mut Command {
+        self.config.expand_args = true;
+        self
+    }
+
     /// Sets the working directory for the child process.
     ///
     /// Note: in case of chroot or pivot root the working directory is set
@@ -99,6 +196,21 @@ impl Command {
         self
     }
 
+    /// Sets a maximum time the child is allowed to run.
+    ///
+    /// `Child::wait` (and therefore `status`) enforces this via
+    /// `wait_with_timeout` in place of a plain `waitpid`: if the child
+    /// (expected to be the init of its own PID namespace) hasn't
+    /// exited by the deadline, it is sent `SIGKILL` and reaped, which
+    /// the kernel also tears the rest of the namespace down with, so
+    /// there's no need to track descendants individually. The caller
+    /// gets an `io::Error` of kind `TimedOut` back rather than an exit
+    /// status.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Command {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
     /// Configuration for the child process's stdin handle (file descriptor 0).
     pub fn stdin(&mut self, cfg: Stdio) -> &mut Command {
         self.stdin = Some(cfg);
@@ -117,4 +229,135 @@ impl Command {
         self
     }
 
+    /// Gives the child a controlling pseudo-terminal instead of the
+    /// `stdin`/`stdout`/`stderr` configuration above.
+    ///
+    /// At spawn, a PTY master/slave pair is allocated, the child becomes
+    /// a session leader via `setsid`, the slave is made its controlling
+    /// terminal, and fds 0/1/2 are all wired to the slave. The master fd
+    /// is handed back to the caller so interactive programs (shells,
+    /// package managers that probe `isatty`) can be driven from outside
+    /// the namespace. `spawn` forks and execs directly and applies no
+    /// namespace or uid-map settings, with or without a pty; explicit
+    /// `stdin`/`stdout`/`stderr` calls are ignored once this is set.
+    pub fn pty(&mut self) -> &mut Command {
+        self.config.pty = Some(PtyConfig::default());
+        self
+    }
+
+    /// Sets the initial window size (`TIOCSWINSZ`) of the pty allocated
+    /// by `pty()`. Has no effect unless `pty()` was also called.
+    pub fn pty_size(&mut self, rows: u16, cols: u16) -> &mut Command {
+        if let Some(ref mut pty) = self.config.pty {
+            pty.rows = rows;
+            pty.cols = cols;
+        }
+        self
+    }
+
+    /// Puts the pty allocated by `pty()` in raw mode instead of the
+    /// default cooked (line-buffered, echoing) mode. Has no effect
+    /// unless `pty()` was also called.
+    pub fn pty_raw(&mut self, raw: bool) -> &mut Command {
+        if let Some(ref mut pty) = self.config.pty {
+            pty.raw = raw;
+        }
+        self
+    }
+
+}
+
+/// Returns true if `path` names a regular file with at least one
+/// executable bit set, used when resolving a bare program name against
+/// `PATH`.
+fn is_executable_file(path: &PathBuf) -> bool {
+    match fs::metadata(path) {
+        Ok(meta) => meta.is_file() && meta.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+/// The interval between `waitpid(WNOHANG)` polls while waiting on a
+/// deadline. There's no portable way to block on both "pid exited" and
+/// "deadline elapsed" at once without a signal handler or a pidfd, so
+/// this polls instead.
+const TIMEOUT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Waits for `pid` to exit, killing and reaping it if `timeout` elapses
+/// first.
+///
+/// Returns `Ok(Some(status))` on a normal exit within the deadline, or
+/// `Ok(None)` if the deadline elapsed and `pid` was killed with
+/// `SIGKILL` and reaped instead. `pid` is expected to be the init of
+/// its own PID namespace, so killing it tears down the whole namespace
+/// rather than leaving orphaned descendants behind.
+pub(crate) fn wait_with_timeout(pid: libc::pid_t, timeout: Duration)
+    -> io::Result<Option<ExitStatus>>
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        let mut raw_status: libc::c_int = 0;
+        let rc = unsafe { libc::waitpid(pid, &mut raw_status, libc::WNOHANG) };
+        if rc < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if rc == pid {
+            return Ok(Some(ExitStatus::from_raw(raw_status)));
+        }
+        if Instant::now() >= deadline {
+            unsafe { libc::kill(pid, libc::SIGKILL); }
+            loop {
+                let rc = unsafe { libc::waitpid(pid, &mut raw_status, 0) };
+                if rc == pid || (rc < 0 && io::Error::last_os_error().kind() != io::ErrorKind::Interrupted) {
+                    break;
+                }
+            }
+            return Ok(None);
+        }
+        thread::sleep(TIMEOUT_POLL_INTERVAL);
+    }
+}
+
+/// Expands `$VAR` and `${VAR}` references in `arg` against `env`,
+/// leaving references that don't resolve untouched. Used at spawn time
+/// when `expand_args()` has been enabled.
+pub(crate) fn expand_env_refs(arg: &OsStr, env: &HashMap<::std::ffi::OsString, ::std::ffi::OsString>)
+    -> ::std::ffi::OsString
+{
+    let text = match arg.to_str() {
+        Some(text) => text,
+        None => return arg.to_os_string(),
+    };
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+        let rest = &text[i + 1..];
+        let (name, skip) = if rest.starts_with('{') {
+            match rest.find('}') {
+                Some(end) => (&rest[1..end], end + 1),
+                None => ("", 0),
+            }
+        } else {
+            let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (&rest[..end], end)
+        };
+        if name.is_empty() {
+            out.push('$');
+            continue;
+        }
+        match env.get(OsStr::new(name)).and_then(|v| v.to_str()) {
+            Some(val) => out.push_str(val),
+            None => {
+                out.push('$');
+                out.push_str(&text[i + 1..i + 1 + skip]);
+            }
+        }
+        while chars.peek().map_or(false, |&(j, _)| j < i + 1 + skip) { chars.next(); }
+    }
+    out.into()
 }
@@ -0,0 +1,90 @@
+// Integration tests for the spawn path in `src/exec.rs`: these actually
+// fork/exec real processes, which is why they live here rather than as
+// `#[cfg(test)]` units inside `src/`.
+extern crate unshare;
+extern crate libc;
+
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::time::Duration;
+
+use unshare::Command;
+
+#[test]
+fn bare_name_is_resolved_against_path() {
+    // "true" has no `/`, so this only succeeds if PATH search kicked in.
+    let status = Command::new("true").status().unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn path_search_can_be_disabled() {
+    let mut cmd = Command::new("true");
+    cmd.allow_path_search(false);
+    // With the literal name handed to execve and no `/` in it, the
+    // kernel looks for "true" relative to the working directory, so
+    // spawning fails outright rather than succeeding via PATH.
+    assert!(cmd.status().is_err());
+}
+
+#[test]
+fn timeout_kills_and_reports_timed_out() {
+    let mut cmd = Command::new("sleep");
+    cmd.arg("5");
+    cmd.timeout(Duration::from_millis(200));
+    let err = cmd.status().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+}
+
+#[test]
+fn timeout_lets_a_quick_child_finish_normally() {
+    let mut cmd = Command::new("true");
+    cmd.timeout(Duration::from_secs(5));
+    let status = cmd.status().unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn pty_hands_back_a_usable_master_fd() {
+    let mut cmd = Command::new("cat");
+    cmd.pty();
+    let mut child = cmd.spawn().unwrap();
+    let master = child.pty_master.expect("pty() should populate pty_master");
+
+    unsafe {
+        let msg = b"hello\n";
+        assert_eq!(libc::write(master, msg.as_ptr() as *const _, msg.len()), msg.len() as isize);
+        let mut buf = [0u8; 64];
+        let n = libc::read(master, buf.as_mut_ptr() as *mut _, buf.len());
+        assert!(n > 0, "expected to read back the echoed line from the pty");
+        // Closing the master hangs up the child's controlling terminal,
+        // so the kernel sends `SIGHUP` to `cat` as its session leader.
+        libc::close(master);
+    }
+    let status = child.wait().unwrap();
+    assert_eq!(status.signal(), Some(libc::SIGHUP));
+}
+
+#[test]
+fn expand_args_resolves_against_the_child_environment() {
+    // `test` never shell-expands its own argv, so this only passes if
+    // `${GREETING}` was expanded by us before `execve`, not left as the
+    // literal `${GREETING}` text the kernel would otherwise see.
+    let mut cmd = Command::new("test");
+    cmd.arg("${GREETING}").arg("=").arg("hello");
+    cmd.env_clear();
+    cmd.env("GREETING", "hello");
+    cmd.expand_args();
+    let status = cmd.status().unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn without_expand_args_the_reference_is_passed_through_literally() {
+    let mut cmd = Command::new("test");
+    cmd.arg("${GREETING}").arg("=").arg("hello");
+    cmd.env_clear();
+    cmd.env("GREETING", "hello");
+    let status = cmd.status().unwrap();
+    assert!(!status.success());
+}